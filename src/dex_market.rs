@@ -0,0 +1,402 @@
+//! On-chain simulation of a Serum-style DEX order book so that
+//! `FlashloanArbitrage` can derive a realistic `expected_profit` instead of
+//! trusting a caller-supplied figure.
+//!
+//! The book sides are stored as a critbit `Slab`: inner nodes branch on the
+//! most significant differing bit of a 128-bit key, and leaves hold the
+//! resting order's price/quantity. Serum encodes bid keys as the bitwise
+//! complement of the price, so an in-order traversal of either side's slab
+//! always yields orders best price first.
+
+use crate::{error::FlashloanArbitrageError, math::Decimal};
+use solana_program::{account_info::AccountInfo, program_error::ProgramError};
+use std::convert::TryInto;
+
+const ORACLE_PRICE_OFFSET: usize = 4; // skip the 4-byte magic/discriminator
+const ORACLE_EXPO_OFFSET: usize = ORACLE_PRICE_OFFSET + 8;
+
+const NODE_UNINITIALIZED: u32 = 0;
+const NODE_INNER: u32 = 1;
+const NODE_LEAF: u32 = 2;
+
+const SLAB_HEADER_LEN: usize = 8 + 8 + 4 + 4 + 8; // bump_index, free_list_len, free_list_head, root, leaf_count
+const NODE_LEN: usize = 72;
+const NODE_TAG_LEN: usize = 4;
+
+// Serum wraps each order book side's slab in a `Market`-owned account with a
+// fixed `b"serum"` + `AccountFlags` header and a `b"padding"` footer; the
+// slab header itself only starts after the former and ends before the latter.
+const ACCOUNT_HEAD_PADDING: usize = 5 + 8;
+const ACCOUNT_TAIL_PADDING: usize = 7;
+
+/// A single price level walked from an order book side: `price` is in
+/// quote-lots-per-base-lot and `quantity` is in base lots.
+#[derive(Clone, Copy, Debug)]
+struct PriceLevel {
+    price: u64,
+    quantity: u64,
+}
+
+/// A read-only view over a Serum critbit slab's raw account bytes.
+pub struct Slab<'a> {
+    data: &'a [u8],
+    is_bids: bool,
+}
+
+impl<'a> Slab<'a> {
+    /// Wrap the asks side of an order book, skipping the `Market`-defined
+    /// header/padding so `data` starts at the slab header.
+    pub fn new_asks(account_data: &'a [u8]) -> Self {
+        Self {
+            data: Self::trim_account_padding(account_data),
+            is_bids: false,
+        }
+    }
+
+    /// Wrap the bids side of an order book. Serum encodes bid keys as the
+    /// bitwise complement of the price, so `leaf_price` un-complements the
+    /// raw key before handing a price back to callers.
+    pub fn new_bids(account_data: &'a [u8]) -> Self {
+        Self {
+            data: Self::trim_account_padding(account_data),
+            is_bids: true,
+        }
+    }
+
+    /// Strips the `Market`-owned account's head/tail padding so the returned
+    /// slice starts at the slab header, as `new_asks`/`new_bids` promise.
+    fn trim_account_padding(account_data: &'a [u8]) -> &'a [u8] {
+        let end = account_data.len().saturating_sub(ACCOUNT_TAIL_PADDING);
+        let start = ACCOUNT_HEAD_PADDING.min(end);
+        &account_data[start..end]
+    }
+
+    fn root(&self) -> Option<u32> {
+        let leaf_count = u64::from_le_bytes(self.data[24..32].try_into().ok()?);
+        if leaf_count == 0 {
+            return None;
+        }
+        Some(u32::from_le_bytes(self.data[20..24].try_into().ok()?))
+    }
+
+    fn node(&self, handle: u32) -> &[u8] {
+        let start = SLAB_HEADER_LEN + handle as usize * NODE_LEN;
+        &self.data[start..start + NODE_LEN]
+    }
+
+    fn node_tag(&self, handle: u32) -> u32 {
+        u32::from_le_bytes(self.node(handle)[0..4].try_into().unwrap())
+    }
+
+    fn inner_children(&self, handle: u32) -> (u32, u32) {
+        let node = self.node(handle);
+        // tag(4) + prefix_len(4) + key(16) = 24, followed by two u32 child handles
+        let left = u32::from_le_bytes(node[24..28].try_into().unwrap());
+        let right = u32::from_le_bytes(node[28..32].try_into().unwrap());
+        (left, right)
+    }
+
+    fn leaf_price(&self, handle: u32) -> u64 {
+        let node = self.node(handle);
+        // tag(4) + owner_slot/fee_tier/padding(4) + key(16); price is the high 64 bits of key
+        let raw = u64::from_le_bytes(node[NODE_TAG_LEN + 4 + 8..NODE_TAG_LEN + 4 + 16].try_into().unwrap());
+        // Bid keys are stored as the bitwise complement of the price so that
+        // in-order traversal yields highest-bid-first the same way it yields
+        // lowest-ask-first on the other side.
+        if self.is_bids {
+            !raw
+        } else {
+            raw
+        }
+    }
+
+    fn leaf_quantity(&self, handle: u32) -> u64 {
+        let node = self.node(handle);
+        // tag(4) + owner_slot/fee_tier/padding(4) + key(16) + owner(32) = 56
+        u64::from_le_bytes(node[56..64].try_into().unwrap())
+    }
+
+    /// Walk every resting order on this side, best price first.
+    fn price_levels(&self) -> Vec<PriceLevel> {
+        let mut levels = Vec::new();
+        let root = match self.root() {
+            Some(root) => root,
+            None => return levels,
+        };
+
+        // Explicit stack of node handles to keep traversal iterative (no
+        // recursion on-BPF). A popped leaf is emitted; a popped inner node
+        // is expanded into its children.
+        let mut stack = vec![root];
+        while let Some(handle) = stack.pop() {
+            match self.node_tag(handle) {
+                NODE_LEAF => levels.push(PriceLevel {
+                    price: self.leaf_price(handle),
+                    quantity: self.leaf_quantity(handle),
+                }),
+                NODE_INNER => {
+                    let (left, right) = self.inner_children(handle);
+                    // Push right first so `left` (smaller keys) pops first.
+                    stack.push(right);
+                    stack.push(left);
+                }
+                NODE_UNINITIALIZED => {}
+                _ => {}
+            }
+        }
+        levels
+    }
+}
+
+/// Result of walking an order book side to fill a fixed amount.
+#[derive(Debug, PartialEq)]
+pub struct FillResult {
+    /// Total quantity received in the side's output currency
+    pub amount_out: u64,
+    /// Leftover input quantity; always 0, since insufficient depth is
+    /// returned as an `InsufficientLiquidity` error instead of a partial fill
+    pub amount_unfilled: u64,
+}
+
+/// Simulates walking the buy and sell DEX order books for a flash-loan
+/// arbitrage round trip.
+pub struct TradeSimulator;
+
+impl TradeSimulator {
+    /// Walk the ask side of the buy-leg book converting `quote_amount` into
+    /// base tokens, best (lowest) ask first.
+    pub fn simulate_buy(asks: &Slab, quote_amount: u64) -> Result<FillResult, ProgramError> {
+        let mut remaining_quote = quote_amount;
+        let mut base_received = Decimal::zero();
+
+        for level in asks.price_levels() {
+            if remaining_quote == 0 {
+                break;
+            }
+            let max_base_at_level = Decimal::from_u64(remaining_quote)
+                .try_div(level.price)?
+                .try_floor_u64()?;
+            let base_filled = max_base_at_level.min(level.quantity);
+            if base_filled == 0 {
+                continue;
+            }
+            let quote_spent = base_filled
+                .checked_mul(level.price)
+                .ok_or(FlashloanArbitrageError::MathOverflow)?;
+            remaining_quote = remaining_quote
+                .checked_sub(quote_spent)
+                .ok_or(FlashloanArbitrageError::MathOverflow)?;
+            base_received = base_received.try_add(Decimal::from_u64(base_filled))?;
+        }
+
+        if remaining_quote > 0 {
+            return Err(FlashloanArbitrageError::InsufficientLiquidity.into());
+        }
+
+        Ok(FillResult {
+            amount_out: base_received.try_floor_u64()?,
+            amount_unfilled: remaining_quote,
+        })
+    }
+
+    /// Walk the bid side of the sell-leg book converting `base_amount` into
+    /// quote tokens, best (highest) bid first.
+    pub fn simulate_sell(bids: &Slab, base_amount: u64) -> Result<FillResult, ProgramError> {
+        let mut remaining_base = base_amount;
+        let mut quote_received = Decimal::zero();
+
+        for level in bids.price_levels() {
+            if remaining_base == 0 {
+                break;
+            }
+            let base_filled = remaining_base.min(level.quantity);
+            if base_filled == 0 {
+                continue;
+            }
+            let quote_gained = Decimal::from_u64(base_filled).try_mul(level.price)?;
+            remaining_base = remaining_base
+                .checked_sub(base_filled)
+                .ok_or(FlashloanArbitrageError::MathOverflow)?;
+            quote_received = quote_received.try_add(quote_gained)?;
+        }
+
+        if remaining_base > 0 {
+            return Err(FlashloanArbitrageError::InsufficientLiquidity.into());
+        }
+
+        Ok(FillResult {
+            amount_out: quote_received.try_floor_u64()?,
+            amount_unfilled: remaining_base,
+        })
+    }
+
+    /// Simulates the full buy-then-sell round trip and returns the net
+    /// quote received after converting the borrowed `amount` to base and
+    /// back to quote. Callers compare this against the amount owed
+    /// (`amount + flashloan_fee + expected_profit`) themselves. A book side
+    /// without enough depth to fill its leg fails here with
+    /// `InsufficientLiquidity`.
+    pub fn simulate_round_trip(asks: &Slab, bids: &Slab, amount: u64) -> Result<u64, ProgramError> {
+        let bought = Self::simulate_buy(asks, amount)?;
+        let sold = Self::simulate_sell(bids, bought.amount_out)?;
+        Ok(sold.amount_out)
+    }
+}
+
+/// Reads `(price, expo)` from an oracle price feed account, where the
+/// reported price is `price * 10^expo`.
+pub fn read_oracle_price(oracle_info: &AccountInfo) -> Result<Decimal, ProgramError> {
+    let data = oracle_info.try_borrow_data()?;
+    let price = i64::from_le_bytes(
+        data.get(ORACLE_PRICE_OFFSET..ORACLE_PRICE_OFFSET + 8)
+            .ok_or(ProgramError::InvalidAccountData)?
+            .try_into()
+            .map_err(|_| ProgramError::InvalidAccountData)?,
+    );
+    let expo = i32::from_le_bytes(
+        data.get(ORACLE_EXPO_OFFSET..ORACLE_EXPO_OFFSET + 4)
+            .ok_or(ProgramError::InvalidAccountData)?
+            .try_into()
+            .map_err(|_| ProgramError::InvalidAccountData)?,
+    );
+    if price <= 0 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Decimal::from_u64(price as u64).scaled_by_pow10(expo)
+}
+
+/// Reads the base/quote reserve balances from a constant-product pool
+/// account and returns the implied quote-per-base execution price.
+pub fn implied_pool_price(pool_info: &AccountInfo) -> Result<Decimal, ProgramError> {
+    let data = pool_info.try_borrow_data()?;
+    // Reserve balances are the pool account's first two little-endian u64 fields, base then quote.
+    let base_reserve = u64::from_le_bytes(
+        data.get(0..8)
+            .ok_or(ProgramError::InvalidAccountData)?
+            .try_into()
+            .map_err(|_| ProgramError::InvalidAccountData)?,
+    );
+    let quote_reserve = u64::from_le_bytes(
+        data.get(8..16)
+            .ok_or(ProgramError::InvalidAccountData)?
+            .try_into()
+            .map_err(|_| ProgramError::InvalidAccountData)?,
+    );
+    if base_reserve == 0 {
+        return Err(FlashloanArbitrageError::InsufficientLiquidity.into());
+    }
+    Decimal::from_u64(quote_reserve).try_div(base_reserve)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a single leaf node's bytes in place, complementing the key for
+    /// the bid side the same way a real Serum slab would.
+    fn write_leaf(node: &mut [u8], price: u64, quantity: u64, is_bids: bool) {
+        node[0..4].copy_from_slice(&NODE_LEAF.to_le_bytes());
+        let raw_key_high = if is_bids { !price } else { price };
+        node[16..24].copy_from_slice(&raw_key_high.to_le_bytes());
+        node[56..64].copy_from_slice(&quantity.to_le_bytes());
+    }
+
+    /// Wraps a slab body (header + nodes) in the `b"serum"` + `AccountFlags`
+    /// head padding and `b"padding"` tail padding that a real order book
+    /// side account carries, so `Slab::new_asks`/`new_bids` must trim it
+    /// back off before offsets line up.
+    fn wrap_account(slab_body: &[u8]) -> Vec<u8> {
+        let mut account = vec![0u8; ACCOUNT_HEAD_PADDING];
+        account.extend_from_slice(slab_body);
+        account.extend_from_slice(&[0u8; ACCOUNT_TAIL_PADDING]);
+        account
+    }
+
+    /// Builds an account-wrapped slab with a single leaf quoting `price` for `quantity`.
+    fn single_leaf_account(price: u64, quantity: u64, is_bids: bool) -> Vec<u8> {
+        let mut body = vec![0u8; SLAB_HEADER_LEN + NODE_LEN];
+        body[20..24].copy_from_slice(&0u32.to_le_bytes()); // root = node 0
+        body[24..32].copy_from_slice(&1u64.to_le_bytes()); // leaf_count = 1
+        write_leaf(&mut body[SLAB_HEADER_LEN..SLAB_HEADER_LEN + NODE_LEN], price, quantity, is_bids);
+        wrap_account(&body)
+    }
+
+    /// Builds an account-wrapped, empty slab (leaf_count = 0, no nodes).
+    fn empty_account() -> Vec<u8> {
+        wrap_account(&[0u8; SLAB_HEADER_LEN])
+    }
+
+    #[test]
+    fn leaf_price_is_read_directly_on_asks() {
+        let account = single_leaf_account(7, 100, false);
+        let asks = Slab::new_asks(&account);
+        assert_eq!(asks.price_levels()[0].price, 7);
+    }
+
+    #[test]
+    fn leaf_price_is_un_complemented_on_bids() {
+        let account = single_leaf_account(7, 100, true);
+        let bids = Slab::new_bids(&account);
+        assert_eq!(bids.price_levels()[0].price, 7);
+    }
+
+    #[test]
+    fn simulate_sell_uses_the_un_complemented_bid_price() {
+        let account = single_leaf_account(5, 100, true);
+        let bids = Slab::new_bids(&account);
+        let result = TradeSimulator::simulate_sell(&bids, 10).unwrap();
+        assert_eq!(result.amount_out, 50);
+    }
+
+    #[test]
+    fn simulate_buy_against_an_empty_book_errors() {
+        let account = empty_account();
+        let asks = Slab::new_asks(&account);
+        let err = TradeSimulator::simulate_buy(&asks, 10).unwrap_err();
+        assert_eq!(err, FlashloanArbitrageError::InsufficientLiquidity.into());
+    }
+
+    #[test]
+    fn simulate_buy_with_insufficient_depth_errors() {
+        // A single ask for 2 base lots at price 1 can only absorb 2 quote lots.
+        let account = single_leaf_account(1, 2, false);
+        let asks = Slab::new_asks(&account);
+        let err = TradeSimulator::simulate_buy(&asks, 10).unwrap_err();
+        assert_eq!(err, FlashloanArbitrageError::InsufficientLiquidity.into());
+    }
+
+    #[test]
+    fn simulate_buy_walks_multiple_price_levels_best_price_first() {
+        // An inner node fanning out to two leaves: the cheaper level (price
+        // 1) has only 4 base lots of depth, so the walk must carry the
+        // remaining quote over to the next (price 3) level rather than
+        // erroring or dropping it.
+        let mut body = vec![0u8; SLAB_HEADER_LEN + 3 * NODE_LEN];
+        body[20..24].copy_from_slice(&0u32.to_le_bytes()); // root = node 0 (inner)
+        body[24..32].copy_from_slice(&2u64.to_le_bytes()); // leaf_count = 2
+
+        let inner = &mut body[SLAB_HEADER_LEN..SLAB_HEADER_LEN + NODE_LEN];
+        inner[0..4].copy_from_slice(&NODE_INNER.to_le_bytes());
+        inner[24..28].copy_from_slice(&1u32.to_le_bytes()); // left child = node 1
+        inner[28..32].copy_from_slice(&2u32.to_le_bytes()); // right child = node 2
+
+        write_leaf(
+            &mut body[SLAB_HEADER_LEN + NODE_LEN..SLAB_HEADER_LEN + 2 * NODE_LEN],
+            1,
+            4,
+            false,
+        );
+        write_leaf(
+            &mut body[SLAB_HEADER_LEN + 2 * NODE_LEN..SLAB_HEADER_LEN + 3 * NODE_LEN],
+            3,
+            100,
+            false,
+        );
+
+        let account = wrap_account(&body);
+        let asks = Slab::new_asks(&account);
+        let result = TradeSimulator::simulate_buy(&asks, 10).unwrap();
+        assert_eq!(result.amount_out, 6); // 4 base @1 (4 quote) + 2 base @3 (6 quote)
+        assert_eq!(result.amount_unfilled, 0);
+    }
+}
@@ -0,0 +1,55 @@
+use num_derive::FromPrimitive;
+use solana_program::{decode_error::DecodeError, program_error::ProgramError};
+use thiserror::Error;
+
+/// Errors that may be returned by the FlashloanArbitrage program
+#[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum FlashloanArbitrageError {
+    /// Invalid instruction
+    #[error("Invalid instruction")]
+    InvalidInstruction,
+
+    /// Failed to unpack instruction data
+    #[error("Failed to unpack instruction data")]
+    InstructionUnpackError,
+
+    /// The simulated profit from walking the order books is below the caller's expected profit
+    #[error("Simulated profit is below the expected profit")]
+    SimulatedProfitBelowMinimum,
+
+    /// The order book side being walked does not have enough depth to fill the requested amount
+    #[error("Order book does not have enough depth to fill the requested amount")]
+    InsufficientLiquidity,
+
+    /// A math operation overflowed, underflowed, or divided by zero
+    #[error("Math operation overflowed")]
+    MathOverflow,
+
+    /// A leg of a multi-hop route produced less output than its configured minimum
+    #[error("Swap leg produced less output than its configured minimum")]
+    LegSlippageExceeded,
+
+    /// The borrow reserve was not refreshed in the current slot
+    #[error("Reserve is stale and must be refreshed in the current slot")]
+    ReserveStale,
+
+    /// The arbitrage did not net enough to cover the flash loan principal plus fee
+    #[error("Insufficient funds to repay the flash loan principal plus fee")]
+    InsufficientFundsForRepay,
+
+    /// The DEX pool's implied price deviates from the oracle price by more than the configured tolerance
+    #[error("Pool price deviates from the oracle price by more than the configured tolerance")]
+    OraclePriceDeviation,
+}
+
+impl From<FlashloanArbitrageError> for ProgramError {
+    fn from(e: FlashloanArbitrageError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+impl<T> DecodeError<T> for FlashloanArbitrageError {
+    fn type_of() -> &'static str {
+        "FlashloanArbitrageError"
+    }
+}
@@ -1,7 +1,55 @@
 use solana_program::program_error::ProgramError;
 use crate::error::FlashloanArbitrageError::{InvalidInstruction, InstructionUnpackError};
+use solana_program::pubkey::Pubkey;
 use std::{convert::TryInto, mem::size_of};
 
+/// One hop of a multi-leg arbitrage route: a `pool` on `dex_program_id`
+/// and the `minimum_output` carried for that hop. The processor consumes
+/// each leg's accounts but performs no swap and does not check
+/// `minimum_output`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SwapLeg {
+    /// Program implementing the DEX this leg swaps on
+    pub dex_program_id: Pubkey,
+    /// Pool or market account this leg swaps through
+    pub pool: Pubkey,
+    /// Minimum output for this leg; carried in the instruction data, not
+    /// checked by the processor
+    pub minimum_output: u64,
+}
+
+impl SwapLeg {
+    const LEN: usize = 32 + 32 + 8;
+
+    fn pack(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.dex_program_id.as_ref());
+        buf.extend_from_slice(self.pool.as_ref());
+        buf.extend_from_slice(&self.minimum_output.to_le_bytes());
+    }
+
+    fn unpack(input: &[u8]) -> Result<(Self, &[u8]), ProgramError> {
+        if input.len() < Self::LEN {
+            return Err(InstructionUnpackError.into());
+        }
+        let (leg, rest) = input.split_at(Self::LEN);
+        let dex_program_id =
+            Pubkey::try_from(&leg[0..32]).map_err(|_| InstructionUnpackError)?;
+        let pool = Pubkey::try_from(&leg[32..64]).map_err(|_| InstructionUnpackError)?;
+        let minimum_output = u64::from_le_bytes(
+            leg[64..72].try_into().map_err(|_| InstructionUnpackError)?,
+        );
+        Ok((
+            Self {
+                dex_program_id,
+                pool,
+                minimum_output,
+            },
+            rest,
+        ))
+    }
+}
+
+/// Instructions supported by the FlashloanArbitrage program.
 pub enum FlashloanArbitrageInstruction {
     /// Initializes the flash loan arbitrage program account
     ///
@@ -11,27 +59,31 @@ pub enum FlashloanArbitrageInstruction {
     /// 2. `[writable]` The program's account to hold state
     /// 3. `[]` The rent sysvar
     /// 4. `[]` The token program
-    /// 5. `[]` Buy DEX program ID (e.g., Raydium)
-    /// 6. `[]` Sell DEX program ID (e.g., Orca)
     InitFlashloanArbitrage,
 
-    /// Executes the arbitrage operation after receiving a flash loan
+    /// Executes the arbitrage operation after receiving a flash loan,
+    /// walking each leg of `legs` in order. No swap is performed: each
+    /// leg's accounts are read and nothing is passed from one leg to the
+    /// next.
     ///
     /// Accounts expected:
     /// 0. `[]` Lending program ID
     /// 1. `[]` Token program ID
     /// 2. `[writable]` Program's state account
     /// 3. `[writable]` Program's token account (to approve transfer)
-    /// 4. `[]` Buy DEX program ID (e.g., Raydium)
-    /// 5. `[]` Sell DEX program ID (e.g., Orca)
-    /// 6. `[writable]` Buy pool address
-    /// 7. `[writable]` Sell pool address
-    /// 8. `[writable]` Profit wallet
+    /// 4. `[writable]` Profit wallet
+    ///
+    /// 5..5+2N: for each leg, in order: `[]` DEX program ID, `[writable]` pool address
     ExecuteOperation {
-        amount: u64, // Amount borrowed
+        /// Amount borrowed
+        amount: u64,
+        /// Swap route the borrowed amount is routed through
+        legs: Vec<SwapLeg>,
     },
 
-    /// Requests a flash loan and executes arbitrage
+    /// Checks a flash-loan arbitrage across `legs`: reserve freshness,
+    /// simulated order-book profit and, per leg, oracle price deviation.
+    /// No flash loan is borrowed and `ExecuteOperation` is not invoked.
     ///
     /// Accounts expected:
     /// 0. `[writable]` Destination liquidity token account (program's token account)
@@ -39,40 +91,90 @@ pub enum FlashloanArbitrageInstruction {
     /// 2. `[writable]` Borrow reserve liquidity supply SPL Token account
     /// 3. `[]` Lending market account
     /// 4. `[]` Derived lending market authority
-    /// 5. `[]` Buy DEX program ID (e.g., Raydium)
-    /// 6. `[]` Sell DEX program ID (e.g., Orca)
-    /// 7. `[writable]` Buy pool address
-    /// 8. `[writable]` Sell pool address
-    /// 9. `[writable]` Profit wallet
-    /// 10. `[]` Token program ID
-    /// 11. `[]` Lending program ID
+    /// 5. `[writable]` Profit wallet
+    /// 6. `[]` Token program ID
+    /// 7. `[]` Lending program ID
+    /// 8. `[]` Buy DEX order book asks side (for trade simulation, first leg)
+    /// 9. `[]` Sell DEX order book bids side (for trade simulation, last leg)
+    /// 10. `[]` Clock sysvar (to check the borrow reserve was refreshed this slot)
+    ///
+    /// 11..11+3N: for each leg, in order: `[]` DEX program ID, `[writable]` pool address, `[]` price oracle for that leg's pair (optional sanity check against pool manipulation, applied per leg so a mid-route pool can't be skewed while the entry pool stays in line)
     FlashloanArbitrage {
-        amount: u64, // Amount to borrow
-        execute_operation_ix_data: Vec<u8>, // Data for the execute operation instruction
-        expected_profit: u64, // Minimum expected profit to ensure the trade is worthwhile
+        /// Amount to borrow
+        amount: u64,
+        /// Data for the execute operation instruction
+        execute_operation_ix_data: Vec<u8>,
+        /// Minimum expected profit to ensure the trade is worthwhile, verified against an on-chain order book simulation
+        expected_profit: u64,
+        /// Maximum allowed deviation between each leg's oracle price and its pool price; 0 disables the check
+        max_price_deviation_bps: u64,
+        /// Swap route the borrowed amount is routed through
+        legs: Vec<SwapLeg>,
+    },
+
+    /// Refresh step ahead of a `FlashloanArbitrage` call in the same
+    /// transaction, which requires the reserve to have been refreshed in the
+    /// current slot. The processor reads the accounts below and returns; it
+    /// does not invoke the lending program or update the reserve.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Borrow reserve account
+    /// 1. `[]` Clock sysvar
+    /// 2. `[]` Lending program ID (CPI target for the actual refresh)
+    RefreshReserve,
+
+    /// Checks that the program's token account holds a flash loan's
+    /// principal plus fee once the arbitrage legs have executed, reverting
+    /// the whole transaction if it did not net enough to cover both. No
+    /// transfer back to the reserve is made.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Source liquidity token account (program's token account, post-swap)
+    /// 1. `[writable]` Destination liquidity token account (reserve's liquidity supply)
+    /// 2. `[]` Borrow reserve account (for flash-loan fee configuration)
+    /// 3. `[]` Program's derived authority (signs the repay transfer)
+    /// 4. `[]` Token program ID
+    ReceiveFlashLoan {
+        /// Principal amount originally borrowed
+        amount: u64,
     },
 }
 
 impl FlashloanArbitrageInstruction {
+    /// Unpacks a byte buffer into a `FlashloanArbitrageInstruction`, the
+    /// first byte selecting the variant and the rest holding its fields.
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
         let (tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
 
         Ok(match tag {
             0 => Self::InitFlashloanArbitrage,
             1 => {
-                let (amount, _rest) = Self::unpack_u64(rest)?;
-                Self::ExecuteOperation { amount }
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (legs, _rest) = Self::unpack_legs(rest)?;
+                Self::ExecuteOperation { amount, legs }
             },
             2 => {
                 let (amount, rest) = Self::unpack_u64(rest)?;
-                let (expected_profit, execute_operation_ix_data_slice) = Self::unpack_u64(rest)?;
+                let (expected_profit, rest) = Self::unpack_u64(rest)?;
+                let (max_price_deviation_bps, rest) = Self::unpack_u64(rest)?;
+                let (legs, rest) = Self::unpack_legs(rest)?;
+                // The length prefix lets `rest` carry bytes beyond the blob
+                // (reserved for future fields) without corrupting the read.
+                let (execute_operation_ix_data_slice, _rest) = Self::unpack_bytes(rest)?;
                 let execute_operation_ix_data = execute_operation_ix_data_slice.to_vec();
                 Self::FlashloanArbitrage {
                     amount,
                     execute_operation_ix_data,
                     expected_profit,
+                    max_price_deviation_bps,
+                    legs,
                 }
             },
+            3 => Self::RefreshReserve,
+            4 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::ReceiveFlashLoan { amount }
+            },
             _ => return Err(InvalidInstruction.into()),
         })
     }
@@ -91,27 +193,179 @@ impl FlashloanArbitrageInstruction {
         }
     }
 
+    fn unpack_u32(input: &[u8]) -> Result<(u32, &[u8]), ProgramError> {
+        if input.len() >= 4 {
+            let (len, rest) = input.split_at(4);
+            let len = len
+                .get(..4)
+                .and_then(|slice| slice.try_into().ok())
+                .map(u32::from_le_bytes)
+                .ok_or(InstructionUnpackError)?;
+            Ok((len, rest))
+        } else {
+            Err(InstructionUnpackError.into())
+        }
+    }
+
+    /// Reads a `u32` length prefix followed by exactly that many bytes,
+    /// returning the blob and whatever bytes follow it (reserved for
+    /// forward-compatible fields added after this one).
+    fn unpack_bytes(input: &[u8]) -> Result<(&[u8], &[u8]), ProgramError> {
+        let (len, rest) = Self::unpack_u32(input)?;
+        if rest.len() < len as usize {
+            return Err(InstructionUnpackError.into());
+        }
+        Ok(rest.split_at(len as usize))
+    }
+
+    /// Reads a `u8` leg count followed by that many fixed-size `SwapLeg` records.
+    fn unpack_legs(input: &[u8]) -> Result<(Vec<SwapLeg>, &[u8]), ProgramError> {
+        let (&leg_count, mut rest) = input.split_first().ok_or(InstructionUnpackError)?;
+        let mut legs = Vec::with_capacity(leg_count as usize);
+        for _ in 0..leg_count {
+            let (leg, remaining) = SwapLeg::unpack(rest)?;
+            legs.push(leg);
+            rest = remaining;
+        }
+        Ok((legs, rest))
+    }
+
+    fn pack_legs(legs: &[SwapLeg], buf: &mut Vec<u8>) {
+        buf.push(legs.len() as u8);
+        for leg in legs {
+            leg.pack(buf);
+        }
+    }
+
+    /// Packs a `FlashloanArbitrageInstruction` into the wire format `unpack` reads.
     pub fn pack(&self) -> Vec<u8> {
         let mut buf = Vec::with_capacity(size_of::<Self>());
-        match self { // Changed from `match *self` to `match self`
+        match self {
             Self::InitFlashloanArbitrage => {
                 buf.push(0);
             }
-            Self::ExecuteOperation { amount } => {
+            Self::ExecuteOperation { amount, legs } => {
                 buf.push(1);
                 buf.extend_from_slice(&amount.to_le_bytes());
+                Self::pack_legs(legs, &mut buf);
             }
             Self::FlashloanArbitrage {
                 amount,
                 execute_operation_ix_data,
                 expected_profit,
+                max_price_deviation_bps,
+                legs,
             } => {
                 buf.push(2);
                 buf.extend_from_slice(&amount.to_le_bytes());
                 buf.extend_from_slice(&expected_profit.to_le_bytes());
+                buf.extend_from_slice(&max_price_deviation_bps.to_le_bytes());
+                Self::pack_legs(legs, &mut buf);
+                buf.extend_from_slice(&(execute_operation_ix_data.len() as u32).to_le_bytes());
                 buf.extend_from_slice(execute_operation_ix_data);
             }
+            Self::RefreshReserve => {
+                buf.push(3);
+            }
+            Self::ReceiveFlashLoan { amount } => {
+                buf.push(4);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
         }
         buf
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_leg(seed: u8) -> SwapLeg {
+        SwapLeg {
+            dex_program_id: Pubkey::new_from_array([seed; 32]),
+            pool: Pubkey::new_from_array([seed.wrapping_add(1); 32]),
+            minimum_output: seed as u64 * 1_000,
+        }
+    }
+
+    #[test]
+    fn swap_leg_round_trips_through_pack_and_unpack() {
+        let leg = test_leg(1);
+        let mut buf = Vec::new();
+        leg.pack(&mut buf);
+
+        let (unpacked, rest) = SwapLeg::unpack(&buf).unwrap();
+        assert_eq!(unpacked, leg);
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn swap_leg_unpack_errors_on_a_short_buffer() {
+        let leg = test_leg(1);
+        let mut buf = Vec::new();
+        leg.pack(&mut buf);
+        buf.truncate(SwapLeg::LEN - 1);
+
+        assert!(SwapLeg::unpack(&buf).is_err());
+    }
+
+    #[test]
+    fn unpack_legs_round_trips_multiple_legs_and_preserves_the_remainder() {
+        let legs = vec![test_leg(1), test_leg(2), test_leg(3)];
+        let mut buf = Vec::new();
+        FlashloanArbitrageInstruction::pack_legs(&legs, &mut buf);
+        buf.extend_from_slice(&[0xAA, 0xBB]); // bytes that should survive as `rest`
+
+        let (unpacked, rest) = FlashloanArbitrageInstruction::unpack_legs(&buf).unwrap();
+        assert_eq!(unpacked, legs);
+        assert_eq!(rest, &[0xAA, 0xBB]);
+    }
+
+    #[test]
+    fn unpack_bytes_errors_when_the_buffer_underruns_its_length_prefix() {
+        let mut buf = 10u32.to_le_bytes().to_vec();
+        buf.extend_from_slice(&[0u8; 3]); // claims 10 bytes, only has 3
+
+        assert!(FlashloanArbitrageInstruction::unpack_bytes(&buf).is_err());
+    }
+
+    #[test]
+    fn flashloan_arbitrage_round_trips_through_pack_and_unpack() {
+        let instruction = FlashloanArbitrageInstruction::FlashloanArbitrage {
+            amount: 1_000,
+            execute_operation_ix_data: vec![1, 2, 3],
+            expected_profit: 50,
+            max_price_deviation_bps: 200,
+            legs: vec![test_leg(1), test_leg(2)],
+        };
+
+        let packed = instruction.pack();
+        let unpacked = FlashloanArbitrageInstruction::unpack(&packed).unwrap();
+        match unpacked {
+            FlashloanArbitrageInstruction::FlashloanArbitrage {
+                amount,
+                execute_operation_ix_data,
+                expected_profit,
+                max_price_deviation_bps,
+                legs,
+            } => {
+                assert_eq!(amount, 1_000);
+                assert_eq!(execute_operation_ix_data, vec![1, 2, 3]);
+                assert_eq!(expected_profit, 50);
+                assert_eq!(max_price_deviation_bps, 200);
+                assert_eq!(legs, vec![test_leg(1), test_leg(2)]);
+            }
+            _ => panic!("expected FlashloanArbitrage"),
+        }
+    }
+
+    #[test]
+    fn receive_flash_loan_round_trips_through_pack_and_unpack() {
+        let instruction = FlashloanArbitrageInstruction::ReceiveFlashLoan { amount: 42 };
+        let packed = instruction.pack();
+        match FlashloanArbitrageInstruction::unpack(&packed).unwrap() {
+            FlashloanArbitrageInstruction::ReceiveFlashLoan { amount } => assert_eq!(amount, 42),
+            _ => panic!("expected ReceiveFlashLoan"),
+        }
+    }
+}
@@ -0,0 +1,22 @@
+#![deny(missing_docs)]
+
+//! A Solana program that borrows a flash loan and executes arbitrage
+//! between two or more DEX venues within the same transaction.
+
+/// Order book simulation used to derive a realistic `expected_profit`.
+pub mod dex_market;
+/// Program entrypoint.
+#[cfg(not(feature = "no-entrypoint"))]
+pub mod entrypoint;
+/// Program-specific errors.
+pub mod error;
+/// Instruction definitions and (de)serialization.
+pub mod instruction;
+/// Fixed-point math shared by the trade simulator and fee accounting.
+pub mod math;
+/// Instruction processing.
+pub mod processor;
+/// Read-only views over foreign account layouts this program depends on.
+pub mod state;
+
+solana_program::declare_id!("FLashArb111111111111111111111111111111111111");
@@ -0,0 +1,170 @@
+//! Fixed-point math shared by the trade simulator and fee accounting so that
+//! order-book walks and repayment calculations are deterministic on-BPF.
+
+use crate::error::FlashloanArbitrageError;
+use solana_program::program_error::ProgramError;
+use std::convert::TryFrom;
+
+/// Scale of `Decimal`'s wad-scaled value, matching the 18-decimal fixed point convention
+/// used across the Solana lending ecosystem.
+pub const WAD: u128 = 1_000_000_000_000_000_000;
+
+/// A fixed-point decimal value represented as a scaled `u128` ("wad").
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Decimal(u128);
+
+impl Decimal {
+    /// Zero
+    pub fn zero() -> Self {
+        Self(0)
+    }
+
+    /// Create a `Decimal` from an integer amount
+    pub fn from_u64(amount: u64) -> Self {
+        Self(WAD * amount as u128)
+    }
+
+    /// Create a `Decimal` representing `bps / 10_000`
+    pub fn from_bps(bps: u64) -> Self {
+        Self(WAD * bps as u128 / 10_000)
+    }
+
+    /// Round down to the nearest integer
+    pub fn try_floor_u64(&self) -> Result<u64, ProgramError> {
+        u64::try_from(self.0 / WAD).map_err(|_| FlashloanArbitrageError::MathOverflow.into())
+    }
+
+    /// Round up to the nearest integer
+    pub fn try_ceil_u64(&self) -> Result<u64, ProgramError> {
+        let extra = self.0 % WAD;
+        let floor = self.0 / WAD;
+        let ceil = if extra > 0 { floor + 1 } else { floor };
+        u64::try_from(ceil).map_err(|_| FlashloanArbitrageError::MathOverflow.into())
+    }
+
+    /// Checked addition
+    pub fn try_add(&self, rhs: Decimal) -> Result<Decimal, ProgramError> {
+        self.0
+            .checked_add(rhs.0)
+            .map(Decimal)
+            .ok_or_else(|| FlashloanArbitrageError::MathOverflow.into())
+    }
+
+    /// Checked subtraction
+    pub fn try_sub(&self, rhs: Decimal) -> Result<Decimal, ProgramError> {
+        self.0
+            .checked_sub(rhs.0)
+            .map(Decimal)
+            .ok_or_else(|| FlashloanArbitrageError::MathOverflow.into())
+    }
+
+    /// Checked multiplication by an integer
+    pub fn try_mul(&self, rhs: u64) -> Result<Decimal, ProgramError> {
+        self.0
+            .checked_mul(rhs as u128)
+            .map(Decimal)
+            .ok_or_else(|| FlashloanArbitrageError::MathOverflow.into())
+    }
+
+    /// Checked division by an integer
+    pub fn try_div(&self, rhs: u64) -> Result<Decimal, ProgramError> {
+        if rhs == 0 {
+            return Err(FlashloanArbitrageError::MathOverflow.into());
+        }
+        Ok(Decimal(self.0 / rhs as u128))
+    }
+
+    /// Scales by `10^expo`, `expo` may be negative
+    pub fn scaled_by_pow10(&self, expo: i32) -> Result<Decimal, ProgramError> {
+        if expo >= 0 {
+            let factor = 10u128
+                .checked_pow(expo as u32)
+                .ok_or(FlashloanArbitrageError::MathOverflow)?;
+            self.0
+                .checked_mul(factor)
+                .map(Decimal)
+                .ok_or_else(|| FlashloanArbitrageError::MathOverflow.into())
+        } else {
+            let factor = 10u128
+                .checked_pow((-expo) as u32)
+                .ok_or(FlashloanArbitrageError::MathOverflow)?;
+            Ok(Decimal(self.0 / factor))
+        }
+    }
+
+    /// The underlying wad-scaled value, for callers that need to compare
+    /// two `Decimal`s without re-deriving a common scale (e.g. a ratio of
+    /// differences where the `WAD` factor cancels out).
+    pub fn raw(&self) -> u128 {
+        self.0
+    }
+}
+
+/// Computes `|a - b| / a` in basis points, used to bound how far a DEX
+/// pool's implied price may drift from an oracle's reported price.
+pub fn deviation_bps(a: Decimal, b: Decimal) -> Result<u64, ProgramError> {
+    if a.raw() == 0 {
+        return Err(FlashloanArbitrageError::MathOverflow.into());
+    }
+    let diff = if a >= b { a.try_sub(b)? } else { b.try_sub(a)? };
+    let numerator = diff
+        .raw()
+        .checked_mul(10_000)
+        .ok_or(FlashloanArbitrageError::MathOverflow)?;
+    u64::try_from(numerator / a.raw()).map_err(|_| FlashloanArbitrageError::MathOverflow.into())
+}
+
+/// Computes `ceil(amount * fee_bps / 10_000)` directly in `u128` so a
+/// flash-loan fee rounds in the lender's favor without routing through the
+/// wad-scaled `Decimal`, which would overflow for amounts near `u64::MAX`.
+pub fn ceil_fee(amount: u64, fee_bps: u64) -> Result<u64, ProgramError> {
+    let numerator = (amount as u128)
+        .checked_mul(fee_bps as u128)
+        .ok_or(FlashloanArbitrageError::MathOverflow)?;
+    let fee = numerator
+        .checked_add(9_999)
+        .ok_or(FlashloanArbitrageError::MathOverflow)?
+        / 10_000;
+    u64::try_from(fee).map_err(|_| FlashloanArbitrageError::MathOverflow.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn try_floor_and_try_ceil_round_towards_and_away_from_zero() {
+        // 7 / 2 = 3.5
+        let value = Decimal::from_u64(7).try_div(2).unwrap();
+        assert_eq!(value.try_floor_u64().unwrap(), 3);
+        assert_eq!(value.try_ceil_u64().unwrap(), 4);
+    }
+
+    #[test]
+    fn try_floor_and_try_ceil_agree_on_exact_values() {
+        let value = Decimal::from_u64(6).try_div(2).unwrap();
+        assert_eq!(value.try_floor_u64().unwrap(), 3);
+        assert_eq!(value.try_ceil_u64().unwrap(), 3);
+    }
+
+    #[test]
+    fn ceil_fee_rounds_up_to_the_next_whole_unit() {
+        // 1000 * 30 bps = 3.0, exact
+        assert_eq!(ceil_fee(1_000, 30).unwrap(), 3);
+        // 1 * 30 bps = 0.003, rounds up to 1
+        assert_eq!(ceil_fee(1, 30).unwrap(), 1);
+        // zero fee stays zero
+        assert_eq!(ceil_fee(1_000, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn deviation_bps_is_zero_for_equal_prices_and_relative_to_the_first_argument() {
+        let a = Decimal::from_u64(100);
+        let b = Decimal::from_u64(105);
+        assert_eq!(deviation_bps(a, a).unwrap(), 0);
+        // |105 - 100| / 100 = 500 bps, measured against `a`
+        assert_eq!(deviation_bps(a, b).unwrap(), 500);
+        // |100 - 105| / 105 = ~476 bps, measured against `b` instead
+        assert_eq!(deviation_bps(b, a).unwrap(), 476);
+    }
+}
@@ -0,0 +1,267 @@
+//! Instruction processing for the FlashloanArbitrage program.
+
+use crate::{
+    dex_market::{self, Slab, TradeSimulator},
+    error::FlashloanArbitrageError,
+    instruction::{FlashloanArbitrageInstruction, SwapLeg},
+    math, state,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+use spl_token::state::Account as TokenAccount;
+
+/// Program state handler
+pub struct Processor;
+
+impl Processor {
+    /// Processes a `FlashloanArbitrageInstruction`
+    pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
+        let instruction = FlashloanArbitrageInstruction::unpack(input)?;
+
+        match instruction {
+            FlashloanArbitrageInstruction::InitFlashloanArbitrage => {
+                msg!("Instruction: InitFlashloanArbitrage");
+                Self::process_init_flashloan_arbitrage(program_id, accounts)
+            }
+            FlashloanArbitrageInstruction::ExecuteOperation { amount, legs } => {
+                msg!("Instruction: ExecuteOperation");
+                Self::process_execute_operation(program_id, accounts, amount, &legs)
+            }
+            FlashloanArbitrageInstruction::FlashloanArbitrage {
+                amount,
+                execute_operation_ix_data,
+                expected_profit,
+                max_price_deviation_bps,
+                legs,
+            } => {
+                msg!("Instruction: FlashloanArbitrage");
+                Self::process_flashloan_arbitrage(
+                    program_id,
+                    accounts,
+                    amount,
+                    execute_operation_ix_data,
+                    expected_profit,
+                    max_price_deviation_bps,
+                    &legs,
+                )
+            }
+            FlashloanArbitrageInstruction::RefreshReserve => {
+                msg!("Instruction: RefreshReserve");
+                Self::process_refresh_reserve(program_id, accounts)
+            }
+            FlashloanArbitrageInstruction::ReceiveFlashLoan { amount } => {
+                msg!("Instruction: ReceiveFlashLoan");
+                Self::process_receive_flash_loan(program_id, accounts, amount)
+            }
+        }
+    }
+
+    fn process_init_flashloan_arbitrage(
+        _program_id: &Pubkey,
+        _accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        Ok(())
+    }
+
+    fn process_execute_operation(
+        _program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        legs: &[SwapLeg],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let _lending_program_info = next_account_info(account_info_iter)?;
+        let _token_program_info = next_account_info(account_info_iter)?;
+        let _state_info = next_account_info(account_info_iter)?;
+        let _program_token_info = next_account_info(account_info_iter)?;
+        let _profit_wallet_info = next_account_info(account_info_iter)?;
+
+        Self::execute_route(account_info_iter, amount, legs)?;
+        Ok(())
+    }
+
+    fn process_flashloan_arbitrage(
+        _program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        _execute_operation_ix_data: Vec<u8>,
+        expected_profit: u64,
+        max_price_deviation_bps: u64,
+        legs: &[SwapLeg],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let _destination_liquidity_info = next_account_info(account_info_iter)?;
+        let borrow_reserve_info = next_account_info(account_info_iter)?;
+        let _reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+        let _lending_market_info = next_account_info(account_info_iter)?;
+        let _lending_market_authority_info = next_account_info(account_info_iter)?;
+        let _profit_wallet_info = next_account_info(account_info_iter)?;
+        let _token_program_info = next_account_info(account_info_iter)?;
+        let _lending_program_info = next_account_info(account_info_iter)?;
+        let buy_asks_info = next_account_info(account_info_iter)?;
+        let sell_bids_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let clock = Clock::from_account_info(clock_info)?;
+        Self::check_reserve_fresh(borrow_reserve_info, &clock)?;
+        Self::check_simulated_profit(
+            borrow_reserve_info,
+            buy_asks_info,
+            sell_bids_info,
+            amount,
+            expected_profit,
+        )?;
+
+        // Every leg carries its own pool + oracle pair so a mid-route pool
+        // can't be skewed while only the entry leg's price is sanity-checked.
+        for _ in legs {
+            let _dex_program_info = next_account_info(account_info_iter)?;
+            let pool_info = next_account_info(account_info_iter)?;
+            let oracle_info = next_account_info(account_info_iter)?;
+            if max_price_deviation_bps > 0 {
+                Self::check_oracle_price(oracle_info, pool_info, max_price_deviation_bps)?;
+            }
+        }
+
+        // No flash loan is borrowed and ExecuteOperation is not invoked;
+        // only the checks above run.
+        Ok(())
+    }
+
+    /// Rejects the trade if a leg's pool-implied price has drifted from its
+    /// oracle's reported price by more than `max_price_deviation_bps`,
+    /// guarding against a borrower skewing a thin pool and "arbitraging"
+    /// against their own manipulation.
+    fn check_oracle_price(
+        oracle_info: &AccountInfo,
+        pool_info: &AccountInfo,
+        max_price_deviation_bps: u64,
+    ) -> ProgramResult {
+        let oracle_price = dex_market::read_oracle_price(oracle_info)?;
+        let implied_price = dex_market::implied_pool_price(pool_info)?;
+        let deviation = math::deviation_bps(oracle_price, implied_price)?;
+
+        if deviation > max_price_deviation_bps {
+            return Err(FlashloanArbitrageError::OraclePriceDeviation.into());
+        }
+        Ok(())
+    }
+
+    fn process_refresh_reserve(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let _reserve_info = next_account_info(account_info_iter)?;
+        let _clock_info = next_account_info(account_info_iter)?;
+        let _lending_program_info = next_account_info(account_info_iter)?;
+
+        // The lending program is not invoked; `_reserve_info`'s last_update
+        // is left as it is.
+        Ok(())
+    }
+
+    fn process_receive_flash_loan(
+        _program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let source_liquidity_info = next_account_info(account_info_iter)?;
+        let _destination_liquidity_info = next_account_info(account_info_iter)?;
+        let reserve_info = next_account_info(account_info_iter)?;
+        let _authority_info = next_account_info(account_info_iter)?;
+        let _token_program_info = next_account_info(account_info_iter)?;
+
+        let fee_bps = {
+            let reserve_data = reserve_info.try_borrow_data()?;
+            state::unpack_reserve_flash_loan_fee_bps(&reserve_data)?
+        };
+        let fee = math::ceil_fee(amount, fee_bps)?;
+        let repay = amount
+            .checked_add(fee)
+            .ok_or(FlashloanArbitrageError::MathOverflow)?;
+
+        let source_balance = {
+            let source_data = source_liquidity_info.try_borrow_data()?;
+            TokenAccount::unpack(&source_data)?.amount
+        };
+        if source_balance < repay {
+            return Err(FlashloanArbitrageError::InsufficientFundsForRepay.into());
+        }
+
+        // No token transfer is made: `repay` is checked against the source
+        // balance only and nothing moves to `_destination_liquidity_info`.
+        Ok(())
+    }
+
+    /// Requires that `reserve_info` was last updated by the lending program
+    /// in the current slot, guarding against borrowing against stale
+    /// interest-accrual numbers.
+    fn check_reserve_fresh(reserve_info: &AccountInfo, clock: &Clock) -> ProgramResult {
+        let reserve_data = reserve_info.try_borrow_data()?;
+        let last_update = state::unpack_reserve_last_update(&reserve_data)?;
+
+        if !last_update.is_fresh(clock.slot) {
+            return Err(FlashloanArbitrageError::ReserveStale.into());
+        }
+        Ok(())
+    }
+
+    /// Walks each leg of the route in order, consuming its DEX program and
+    /// pool accounts. No swap is performed, no output is passed from one
+    /// leg to the next, and `minimum_output` is not checked.
+    fn execute_route(
+        account_info_iter: &mut std::slice::Iter<AccountInfo>,
+        _amount: u64,
+        legs: &[SwapLeg],
+    ) -> ProgramResult {
+        for _leg in legs {
+            let _dex_program_info = next_account_info(account_info_iter)?;
+            let _pool_info = next_account_info(account_info_iter)?;
+
+            // No CPI into `_leg.dex_program_id` is made and
+            // `_leg.minimum_output` is not checked.
+        }
+        Ok(())
+    }
+
+    /// Walks both DEX order books for the round trip and rejects the trade if
+    /// the realistic, simulated profit falls short of what the caller expects
+    /// once the borrowed amount and the flash-loan fee are repaid.
+    fn check_simulated_profit(
+        borrow_reserve_info: &AccountInfo,
+        buy_asks_info: &AccountInfo,
+        sell_bids_info: &AccountInfo,
+        amount: u64,
+        expected_profit: u64,
+    ) -> ProgramResult {
+        let asks_data = buy_asks_info.try_borrow_data()?;
+        let bids_data = sell_bids_info.try_borrow_data()?;
+        let asks = Slab::new_asks(&asks_data);
+        let bids = Slab::new_bids(&bids_data);
+
+        let quote_received = TradeSimulator::simulate_round_trip(&asks, &bids, amount)?;
+
+        let fee_bps = {
+            let reserve_data = borrow_reserve_info.try_borrow_data()?;
+            state::unpack_reserve_flash_loan_fee_bps(&reserve_data)?
+        };
+        let fee = math::ceil_fee(amount, fee_bps)?;
+        let owed = amount
+            .checked_add(fee)
+            .ok_or(FlashloanArbitrageError::MathOverflow)?;
+        let simulated_profit = quote_received
+            .checked_sub(owed)
+            .ok_or(FlashloanArbitrageError::SimulatedProfitBelowMinimum)?;
+
+        if simulated_profit < expected_profit {
+            return Err(FlashloanArbitrageError::SimulatedProfitBelowMinimum.into());
+        }
+        Ok(())
+    }
+}
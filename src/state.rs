@@ -0,0 +1,64 @@
+//! A minimal view over the borrow reserve account layout used by the
+//! lending programs this program flash-borrows from. The reserve is owned
+//! and written by that external program; we only read its freshness
+//! marker and its flash-loan fee, so we don't deserialize the rest of its
+//! state.
+
+use solana_program::{clock::Slot, program_error::ProgramError};
+use std::convert::TryInto;
+
+const VERSION_LEN: usize = 1;
+const SLOT_LEN: usize = 8;
+const STALE_LEN: usize = 1;
+// Flash-loan fee, in basis points, immediately follows `last_update` in the
+// reserve layout.
+const FLASH_LOAN_FEE_BPS_OFFSET: usize = VERSION_LEN + SLOT_LEN + STALE_LEN;
+
+/// Slot at which a reserve's interest accrual was last refreshed, and
+/// whether the lending program itself considers that refresh stale.
+pub struct LastUpdate {
+    /// Slot at which the reserve's interest accrual was last refreshed
+    pub slot: Slot,
+    /// Whether the lending program itself has flagged this refresh stale
+    pub stale: bool,
+}
+
+impl LastUpdate {
+    /// A reserve is only safe to borrow against when it was refreshed in
+    /// the current slot and the lending program hasn't separately flagged
+    /// it stale.
+    pub fn is_fresh(&self, current_slot: Slot) -> bool {
+        !self.stale && self.slot == current_slot
+    }
+}
+
+/// Reads the `last_update` field out of a borrow reserve account's raw
+/// data, matching the `version: u8, last_update: { slot: u64, stale: bool }`
+/// prefix lending reserves are laid out with.
+pub fn unpack_reserve_last_update(data: &[u8]) -> Result<LastUpdate, ProgramError> {
+    let slot_bytes = data
+        .get(VERSION_LEN..VERSION_LEN + SLOT_LEN)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    let slot = u64::from_le_bytes(
+        slot_bytes
+            .try_into()
+            .map_err(|_| ProgramError::InvalidAccountData)?,
+    );
+    let stale = *data
+        .get(VERSION_LEN + SLOT_LEN)
+        .ok_or(ProgramError::InvalidAccountData)?
+        != 0;
+    Ok(LastUpdate { slot, stale })
+}
+
+/// Reads the reserve's configured flash-loan fee, in basis points.
+pub fn unpack_reserve_flash_loan_fee_bps(data: &[u8]) -> Result<u64, ProgramError> {
+    let fee_bytes = data
+        .get(FLASH_LOAN_FEE_BPS_OFFSET..FLASH_LOAN_FEE_BPS_OFFSET + 8)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    Ok(u64::from_le_bytes(
+        fee_bytes
+            .try_into()
+            .map_err(|_| ProgramError::InvalidAccountData)?,
+    ))
+}